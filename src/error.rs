@@ -6,34 +6,6 @@ use axum::{
 use std::fmt::Debug;
 use thiserror::Error;
 
-#[derive(Error)]
-pub enum ApiError {
-    #[error("Route Level Error")]
-    UnexpectedError(#[from] color_eyre::Report),
-}
-
-impl Debug for ApiError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ApiError::UnexpectedError(err) => {
-                // tracing::error!("Unexpected: {}", err);
-                error_chain_fmt(err, f)
-            }
-        }
-    }
-}
-
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response<BoxBody> {
-        let status = StatusCode::INTERNAL_SERVER_ERROR;
-        let body = format!("{}: {}\n\n{:?}", status, self, self);
-        let mut response = (status, body).into_response();
-
-        response.extensions_mut().insert(self);
-        response
-    }
-}
-
 #[derive(Error)]
 pub enum TopError {
     #[error("Top Level Error")]
@@ -78,6 +50,100 @@ impl Debug for BottomError {
     }
 }
 
+/// Classifies an [`ApiError`] for HTTP status purposes without exposing the
+/// underlying cause to callers.
+#[derive(Clone, Copy)]
+enum ErrorClassification {
+    BadRequest,
+    NotFound,
+    Unexpected,
+}
+
+/// An opaque API error: callers match on [`ApiError::is_client`],
+/// [`ApiError::is_server`], [`ApiError::is_not_found`], or
+/// [`ApiError::status_code`] instead of a dedicated variant per failure mode,
+/// so the error's internal representation can grow (more classifications,
+/// more context on `source`) without breaking anything that handles it.
+pub struct ApiError {
+    classification: ErrorClassification,
+    source: color_eyre::Report,
+}
+
+impl ApiError {
+    pub fn bad_request(source: impl Into<color_eyre::Report>) -> Self {
+        Self {
+            classification: ErrorClassification::BadRequest,
+            source: source.into(),
+        }
+    }
+
+    pub fn not_found(source: impl Into<color_eyre::Report>) -> Self {
+        Self {
+            classification: ErrorClassification::NotFound,
+            source: source.into(),
+        }
+    }
+
+    pub fn unexpected(source: impl Into<color_eyre::Report>) -> Self {
+        Self {
+            classification: ErrorClassification::Unexpected,
+            source: source.into(),
+        }
+    }
+
+    pub fn is_client(&self) -> bool {
+        matches!(
+            self.classification,
+            ErrorClassification::BadRequest | ErrorClassification::NotFound
+        )
+    }
+
+    pub fn is_server(&self) -> bool {
+        matches!(self.classification, ErrorClassification::Unexpected)
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.classification, ErrorClassification::NotFound)
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self.classification {
+            ErrorClassification::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorClassification::NotFound => StatusCode::NOT_FOUND,
+            ErrorClassification::Unexpected => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.classification {
+            ErrorClassification::BadRequest => write!(f, "Bad Request"),
+            ErrorClassification::NotFound => write!(f, "Not Found"),
+            ErrorClassification::Unexpected => write!(f, "Route Level Error"),
+        }
+    }
+}
+
+impl Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(&self.source, f)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response<BoxBody> {
+        let status = self.status_code();
+        let body = format!("{}: {}\n\n{:?}", status, self, self);
+        let mut response = (status, body).into_response();
+
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
 pub struct BadError(pub std::io::Error);
 
 impl std::error::Error for BadError {
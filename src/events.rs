@@ -0,0 +1,34 @@
+use tokio::sync::broadcast;
+
+/// Default number of not-yet-delivered events a slow subscriber can fall
+/// behind by before it starts missing them.
+const CHANNEL_CAPACITY: usize = 100;
+
+/// A simple broadcast pub-sub channel backing the SSE route: anything
+/// published here is fanned out to every current subscriber.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<String>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: impl Into<String>) {
+        // No subscribers is not an error - the event is simply dropped.
+        let _ = self.sender.send(event.into());
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
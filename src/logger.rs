@@ -1,53 +1,113 @@
 use axum::{
     body::Body,
+    error_handling::HandleErrorLayer,
     extract::{ConnectInfo, MatchedPath, OriginalUri},
     http::Request,
+    middleware::{self, Next},
     response::Response,
     Router,
 };
-use hyper::{http::HeaderName, Version, body::Bytes, HeaderMap};
+use color_eyre::eyre::eyre;
+use hyper::{
+    body::Bytes,
+    header::{CONTENT_ENCODING, CONTENT_LENGTH},
+    http::HeaderName,
+    HeaderMap, Version,
+};
+use opentelemetry::{
+    global,
+    sdk::{propagation::TraceContextPropagator, trace as sdktrace, Resource},
+    KeyValue,
+};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
 use std::{borrow::Cow, net::SocketAddr, time::Duration};
-use tower::ServiceBuilder;
+use tower::{BoxError, ServiceBuilder};
 use tracing::subscriber::set_global_default;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::{
+    fmt::MakeWriter, layer::SubscriberExt, registry::LookupSpan, EnvFilter, Registry,
+};
+use uuid::Uuid;
 
 use tower_http::{
+    compression::{
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+        CompressionLayer,
+    },
     request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
     trace::TraceLayer, classify::ServerErrorsFailureClass,
 };
 use tracing::{Span, Subscriber};
 
-use crate::error::{ApiError, OpaqueApiError};
+use crate::error::ApiError;
 
 // region: init telemetry
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
+    otlp: bool,
 ) -> impl Subscriber + Send + Sync
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
+    // Registered unconditionally - independent of `otlp`. Parenting a span on
+    // an inbound `traceparent` (`remote_parent_context`) and injecting an
+    // outbound one (`propagate_trace_context`) also need the
+    // `OpenTelemetryLayer` below, so with `otlp` off inbound headers are
+    // ignored and no `traceparent` is sent back.
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    let formatting_layer = BunyanFormattingLayer::new(name.clone(), sink);
+    let otel_layer = otlp.then(|| OpenTelemetryLayer::new(build_otlp_tracer(&name)));
 
     Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
         .with(formatting_layer)
+        .with(otel_layer)
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to set logger.");
     set_global_default(subscriber).expect("Failed to set subscriber.");
 }
+
+/// Installs a batched OTLP-over-gRPC exporter. The W3C `traceparent`/
+/// `tracestate` propagator used by [`remote_parent_context`] and
+/// [`propagate_trace_context`] is registered unconditionally in
+/// [`get_subscriber`], but spans only carry an OpenTelemetry context - and so
+/// only join or propagate a trace - when this tracer's layer is installed.
+fn build_otlp_tracer(name: &str) -> sdktrace::Tracer {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+        .with_trace_config(
+            sdktrace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                name.to_string(),
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)
+        .expect("Failed to install OTLP tracer")
+}
+
+/// Call on shutdown so batched spans are flushed to the collector instead of
+/// being dropped with the process.
+pub fn shutdown_tracer() {
+    global::shutdown_tracer_provider();
+}
 // endregion: init telemetry
 
 // region: telemetry middleware
-pub async fn add_telemetry(route: Router) -> Router {
+pub async fn add_telemetry<S>(route: Router<S>, server_id: Uuid) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
     route.layer(
         ServiceBuilder::new()
             .layer(SetRequestIdLayer::new(
@@ -89,7 +149,7 @@ pub async fn add_telemetry(route: Router) -> Router {
                             .and_then(|id| id.header_value().to_str().ok())
                             .unwrap_or("");
 
-                        tracing::info_span!(
+                        let span = tracing::info_span!(
                             "http request",
                             http.method = %http_method,
                             http.route = %http_route,
@@ -105,7 +165,19 @@ pub async fn add_telemetry(route: Router) -> Router {
                             request_id = %request_id,
                             exception.message = tracing::field::Empty,
                             exception.details = tracing::field::Empty,
-                        )
+                            http.response_encoding = tracing::field::Empty,
+                            http.response_size = tracing::field::Empty,
+                            http.error_kind = tracing::field::Empty,
+                            http.stream_events = tracing::field::Empty,
+                            http.stream_bytes = tracing::field::Empty,
+                            http.stream_duration_ms = tracing::field::Empty,
+                        );
+
+                        // Stitch into an upstream trace if the caller sent a valid
+                        // `traceparent`; a malformed or unsupported-version header
+                        // yields an empty context, so the span just stays a root.
+                        span.set_parent(remote_parent_context(request.headers()));
+                        span
                     })
                     .on_request(|_request: &Request<_>, _span: &Span| {
                         // nothing to see here ...
@@ -114,14 +186,9 @@ pub async fn add_telemetry(route: Router) -> Router {
                         |response: &Response, _latency: Duration, span: &Span| {
                             let mut display = String::new();
                             let mut debug = String::new();
-                            
-                            if let Some(response_error) = response.extensions().get::<ApiError>() {
-                                // pre-formatting errors is a workaround for https://github.com/tokio-rs/tracing/issues/1565
-                                display = format!("{response_error}");
-                                debug = format!("{response_error:?}");
-                            }
 
-                            if let Some(response_error) = response.extensions().get::<OpaqueApiError>() {
+                            let api_error = response.extensions().get::<ApiError>();
+                            if let Some(response_error) = api_error {
                                 // pre-formatting errors is a workaround for https://github.com/tokio-rs/tracing/issues/1565
                                 display = format!("{response_error}");
                                 debug = format!("{response_error:?}");
@@ -129,51 +196,247 @@ pub async fn add_telemetry(route: Router) -> Router {
 
                             // Record the response's status code in the span.
                             span.record("http.status_code", response.status().as_u16());
-                            
-                            match response.status() {
-                                // 2xx is fine!
-                                code if code.is_success() => {
-                                    span.record("exception.message", "");
-                                    span.record("exception.details", "");
-                                    span.record("otel.status_code", "OK");
-                                }
-                                // 4xx is a client error.
-                                code if code.is_client_error() => {
-                                    span.record("exception.message", display);
-                                    span.record("exception.details", debug);
-                                    span.record("otel.status_code", "OK");
-                                }
-                                // 5xx is a server error.
-                                code if code.is_server_error() => {
-                                    span.record("exception.message", display);
-                                    span.record("exception.details", debug);
-                                    span.record("otel.status_code", "ERROR");
-                                }
-                                // Responses with any other code are unexpected, so
-                                // we'll mark the span as an error.
-                                _ => {
-                                    span.record("exception.message", "Unexpected Error");
-                                    span.record("exception.details", debug);
-                                    span.record("otel.status_code", "ERROR");
+
+                            // `CompressionLayer` negotiates the encoding per the
+                            // client's `Accept-Encoding`; surface what it picked.
+                            let response_encoding = response
+                                .headers()
+                                .get(CONTENT_ENCODING)
+                                .and_then(|value| value.to_str().ok())
+                                .unwrap_or("identity");
+                            span.record("http.response_encoding", response_encoding);
+
+                            // Fixed-length, non-chunked HTTP/1 responses never
+                            // reach `on_eos` - hyper skips `poll_trailers` once
+                            // `is_end_stream()` is already true after the last
+                            // data frame - so record the size here whenever
+                            // `Content-Length` is actually on the wire.
+                            // Compressed/streamed bodies usually drop it up
+                            // front; `on_eos`/`on_failure` fill in the wire-byte
+                            // tally from `on_body_chunk` for those instead.
+                            let response_size = response
+                                .headers()
+                                .get(CONTENT_LENGTH)
+                                .and_then(|value| value.to_str().ok())
+                                .and_then(|value| value.parse::<u64>().ok());
+                            if let Some(response_size) = response_size {
+                                span.record("http.response_size", response_size);
+                            }
+
+                            if let Some(error) = api_error {
+                                // `ApiError` already knows whether it's a
+                                // client or server error, so trust that instead
+                                // of re-deriving it from the status family.
+                                span.record("exception.message", display);
+                                span.record("exception.details", debug);
+                                span.record(
+                                    "otel.status_code",
+                                    if error.is_server() { "ERROR" } else { "OK" },
+                                );
+                                // Surfaces the same classification as a
+                                // queryable field instead of just folding it
+                                // into the free-text `exception.message`.
+                                span.record(
+                                    "http.error_kind",
+                                    if error.is_not_found() {
+                                        "not_found"
+                                    } else if error.is_client() {
+                                        "bad_request"
+                                    } else {
+                                        "unexpected"
+                                    },
+                                );
+                            } else {
+                                match response.status() {
+                                    // 2xx is fine!
+                                    code if code.is_success() => {
+                                        span.record("exception.message", "");
+                                        span.record("exception.details", "");
+                                        span.record("otel.status_code", "OK");
+                                    }
+                                    // 4xx is a client error.
+                                    code if code.is_client_error() => {
+                                        span.record("exception.message", display);
+                                        span.record("exception.details", debug);
+                                        span.record("otel.status_code", "OK");
+                                    }
+                                    // 5xx is a server error.
+                                    code if code.is_server_error() => {
+                                        span.record("exception.message", display);
+                                        span.record("exception.details", debug);
+                                        span.record("otel.status_code", "ERROR");
+                                    }
+                                    // Responses with any other code are unexpected, so
+                                    // we'll mark the span as an error.
+                                    _ => {
+                                        span.record("exception.message", "Unexpected Error");
+                                        span.record("exception.details", debug);
+                                        span.record("otel.status_code", "ERROR");
+                                    }
                                 }
-                            }                           
-                    }) 
-                    .on_body_chunk(|_chunk: &Bytes, _latency: Duration, _span: &Span| {
-                        // ...
+                            }
                     })
-                    .on_eos(
-                        |_trailers: Option<&HeaderMap>, _stream_duration: Duration, _span: &Span| {
-                        // ...
+                    .on_body_chunk(|chunk: &Bytes, _latency: Duration, span: &Span| {
+                        // `TraceLayer` sits outside `CompressionLayer` below, so
+                        // every chunk here is already in its negotiated wire
+                        // encoding - there's no vantage point left from which to
+                        // also see pre-compression bytes, so this tallies wire
+                        // bytes only and makes no attempt at a compression-ratio
+                        // field. `http.response_encoding` above is the rest of
+                        // the picture this layering can offer.
+                        //
+                        // `handler_events` counts logical SSE `Event`s itself
+                        // (via `record_stream_event`, at the stream source,
+                        // before `KeepAlive` splices in its own comment-frame
+                        // pings) so those pings inflate `http.stream_bytes` but
+                        // not `http.stream_events`.
+                        record_response_chunk(span, chunk.len() as u64);
                     })
-                    .on_failure(|_error: ServerErrorsFailureClass, _latency: Duration, _span: &Span| {
-                        // ...
+                    .on_eos(
+                        |_trailers: Option<&HeaderMap>, stream_duration: Duration, span: &Span| {
+                            let stats = take_response_stream_stats(span);
+                            span.record("http.response_size", stats.bytes);
+                            span.record("http.stream_events", stats.events);
+                            span.record("http.stream_bytes", stats.bytes);
+                            span.record(
+                                "http.stream_duration_ms",
+                                stream_duration.as_millis() as u64,
+                            );
+                            span.record("otel.status_code", "OK");
+                        },
+                    )
+                    .on_failure(|_error: ServerErrorsFailureClass, stream_duration: Duration, span: &Span| {
+                        let stats = take_response_stream_stats(span);
+                        // A 5xx response is reported here right after
+                        // `on_response`, before any body chunk; keep the
+                        // `Content-Length` size recorded there in that case.
+                        if stats.bytes > 0 {
+                            span.record("http.response_size", stats.bytes);
+                        }
+                        span.record("http.stream_events", stats.events);
+                        span.record("http.stream_bytes", stats.bytes);
+                        span.record("http.stream_duration_ms", stream_duration.as_millis() as u64);
+                        span.record("otel.status_code", "ERROR");
                     })
             )
             .layer(PropagateRequestIdLayer::new(
                 HeaderName::from_static("x-request-id"),
             ))
+            .layer(middleware::from_fn(propagate_trace_context))
+            // SSE bodies are excluded, so each DATA frame `on_body_chunk` sees
+            // above is one uncompressed `Event` or `KeepAlive` ping, not a
+            // re-chunked compression output frame.
+            .layer(CompressionLayer::new().compress_when(
+                DefaultPredicate::new().and(NotForContentType::new("text/event-stream")),
+            ))
+            // Everything below this boundary (load-shedding, the concurrency
+            // cap, the timeout) can fail; `HandleErrorLayer` converts that
+            // `BoxError` into an `ApiError` so it flows through the same
+            // `on_response` recording above instead of axum's default
+            // 500-with-no-body. `server_id` is only used for a decorative log
+            // line, so it's captured by the closure rather than extracted via
+            // `State` - `HandleErrorLayer`'s handler runs outside `Router`'s
+            // state, so a `State<Uuid>` extractor would fail both to compile
+            // and, if it compiled, to resolve at runtime.
+            .layer(HandleErrorLayer::new(
+                move |matched_path: Option<MatchedPath>, err: BoxError| {
+                    handle_middleware_error(server_id, matched_path, err)
+                },
+            ))
+            .load_shed()
+            .concurrency_limit(64)
+            .timeout(Duration::from_secs(30))
     )
 }
+
+async fn handle_middleware_error(
+    server_id: Uuid,
+    matched_path: Option<MatchedPath>,
+    err: BoxError,
+) -> ApiError {
+    tracing::info!("Server ID: {}", server_id);
+    let route = matched_path.as_ref().map(MatchedPath::as_str).unwrap_or("unknown");
+
+    if err.is::<tower::timeout::error::Elapsed>() {
+        ApiError::unexpected(eyre!("`{route}` timed out"))
+    } else if err.is::<tower::load_shed::error::Overloaded>() {
+        ApiError::unexpected(eyre!("`{route}` is overloaded, shed the request"))
+    } else {
+        ApiError::unexpected(err)
+    }
+}
+
+/// Extracts a remote [`opentelemetry::Context`] from an inbound `traceparent`
+/// (and, if present, `tracestate`) header. Falls back to an empty context -
+/// i.e. a fresh root span - when the header is missing, malformed, or uses an
+/// unsupported version, per the W3C Trace Context spec.
+fn remote_parent_context(headers: &HeaderMap) -> opentelemetry::Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Running totals seen across a response's `on_body_chunk` callbacks,
+/// stashed in the span's extensions so they survive until `on_eos`/
+/// `on_failure` read them back.
+#[derive(Default, Clone, Copy)]
+struct ResponseStreamStats {
+    events: u64,
+    bytes: u64,
+}
+
+fn record_response_chunk(span: &Span, len: u64) {
+    with_response_stream_stats(span, |stats| stats.bytes += len);
+}
+
+/// Called from `handler_events`'s stream for each real domain message, before
+/// `KeepAlive` splices in its own comment-frame pings - so `http.stream_events`
+/// counts emitted `Event`s, not every chunk that happens to cross the wire.
+pub(crate) fn record_stream_event(span: &Span) {
+    with_response_stream_stats(span, |stats| stats.events += 1);
+}
+
+fn with_response_stream_stats(span: &Span, update: impl FnOnce(&mut ResponseStreamStats)) {
+    let Some(id) = span.id() else { return };
+    tracing::dispatcher::get_default(|dispatch| {
+        let Some(registry) = dispatch.downcast_ref::<Registry>() else { return };
+        let Some(span_ref) = registry.span(&id) else { return };
+        let mut extensions = span_ref.extensions_mut();
+        match extensions.get_mut::<ResponseStreamStats>() {
+            Some(stats) => update(stats),
+            None => {
+                let mut stats = ResponseStreamStats::default();
+                update(&mut stats);
+                extensions.insert(stats);
+            }
+        }
+    });
+}
+
+/// Reads back (and clears) the totals `record_response_chunk` accumulated,
+/// defaulting to zero for responses that never streamed a body chunk.
+fn take_response_stream_stats(span: &Span) -> ResponseStreamStats {
+    let Some(id) = span.id() else {
+        return ResponseStreamStats::default();
+    };
+    tracing::dispatcher::get_default(|dispatch| {
+        dispatch
+            .downcast_ref::<Registry>()
+            .and_then(|registry| registry.span(&id))
+            .and_then(|span_ref| span_ref.extensions_mut().remove::<ResponseStreamStats>())
+            .unwrap_or_default()
+    })
+}
+
+/// Injects the current span's context back onto the response as a
+/// `traceparent` header, so downstream/upstream callers can continue the
+/// trace started (or joined) by `make_span_with`.
+async fn propagate_trace_context<B>(request: Request<B>, next: Next<B>) -> Response {
+    let mut response = next.run(request).await;
+    let cx = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HeaderInjector(response.headers_mut()));
+    });
+    response
+}
 // endregion: telemetry middleware
 
 #[inline]
@@ -3,22 +3,29 @@ use std::net::{SocketAddr, TcpListener};
 use uuid::Uuid;
 
 mod error;
+mod events;
 mod logger;
 mod routes;
 
+use events::EventBus;
+
 #[derive(Clone, Debug, FromRef)]
 struct AppState {
     server_id: Uuid,
+    events: EventBus,
 }
 
 #[tokio::main]
 async fn main() {
-    let subscriber = logger::get_subscriber("zero2axum".into(), "info".into(), std::io::stdout);
+    let otlp = std::env::var("OTLP_ENABLED").is_ok();
+    let subscriber =
+        logger::get_subscriber("zero2axum".into(), "info".into(), std::io::stdout, otlp);
     logger::init_subscriber(subscriber);
     color_eyre::install().unwrap();
 
     let state = AppState {
         server_id: Uuid::new_v4(),
+        events: EventBus::new(),
     };
 
     let mut app = Router::new()
@@ -27,16 +34,33 @@ async fn main() {
         .route("/query", get(routes::handler_query))
         .route("/error", get(routes::handler_error))
         .route("/error/opaque", get(routes::handler_error_opaque))
-        .with_state(state)
+        .route(
+            "/events",
+            get(routes::handler_events).post(routes::handler_publish_event),
+        )
         .fallback(routes::fallback);
 
-    app = logger::add_telemetry(app).await;
+    // `server_id` is passed in directly (rather than extracted from router
+    // state) since `add_telemetry` runs before `with_state` below.
+    app = logger::add_telemetry(app, state.server_id).await;
+
+    let app = app.with_state(state);
 
     let listener = TcpListener::bind("127.0.0.1:3000").unwrap();
     tracing::info!("listening on {}", listener.local_addr().unwrap());
     axum::Server::from_tcp(listener)
         .unwrap()
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
+
+    // Flush any batched spans to the collector before the process exits.
+    logger::shutdown_tracer();
+}
+
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Failed to install Ctrl+C handler");
 }
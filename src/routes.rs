@@ -2,14 +2,23 @@ use axum::{
     debug_handler,
     extract::{Query, State},
     http::{StatusCode, Uri},
-    response::{Html, IntoResponse},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse,
+    },
 };
 
 use color_eyre::eyre::eyre;
+use futures_core::Stream;
 use serde::Deserialize;
+use std::{convert::Infallible, time::Duration};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tracing::Span;
 use uuid::Uuid;
 
 use crate::error::{ApiError, BadError, BottomError, MiddleError, TopError};
+use crate::events::EventBus;
+use crate::logger::record_stream_event;
 
 #[debug_handler]
 pub async fn handler() -> Html<&'static str> {
@@ -43,7 +52,56 @@ pub async fn fallback(uri: Uri) -> (StatusCode, String) {
 #[tracing::instrument(skip(server_id))]
 pub async fn handler_error(State(server_id): State<Uuid>) -> Result<(), ApiError> {
     tracing::info!("Server ID: {}", server_id);
-    top_error().map_err(|err| ApiError::UnexpectedError(eyre!(err)))
+    top_error().map_err(|err| ApiError::unexpected(eyre!(err)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpaqueErrorQuery {
+    kind: Option<String>,
+}
+
+#[debug_handler]
+#[tracing::instrument(skip(server_id))]
+pub async fn handler_error_opaque(
+    State(server_id): State<Uuid>,
+    Query(query): Query<OpaqueErrorQuery>,
+) -> Result<(), ApiError> {
+    tracing::info!("Server ID: {}", server_id);
+    match query.kind.as_deref() {
+        Some("not_found") => Err(ApiError::not_found(eyre!("no such resource"))),
+        Some("bad_request") => Err(ApiError::bad_request(eyre!("invalid request"))),
+        _ => Err(ApiError::unexpected(eyre!("Dinosaurs Mating"))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishEvent {
+    message: String,
+}
+
+#[debug_handler]
+#[tracing::instrument(skip(events))]
+pub async fn handler_publish_event(
+    State(events): State<EventBus>,
+    Query(event): Query<PublishEvent>,
+) -> StatusCode {
+    events.publish(event.message);
+    StatusCode::ACCEPTED
+}
+
+#[debug_handler]
+pub async fn handler_events(
+    State(events): State<EventBus>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // Tally real events here, before `KeepAlive` splices in its own
+    // comment-frame pings below - see `record_stream_event`.
+    let span = Span::current();
+    let stream = BroadcastStream::new(events.subscribe())
+        .filter_map(|message| message.ok())
+        .inspect(move |_| record_stream_event(&span))
+        .map(|message| Ok(Event::default().data(message)));
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
 }
 
 fn top_error() -> Result<(), TopError> {